@@ -0,0 +1,16 @@
+use std::env;
+
+fn main() {
+    let target = env::var("TARGET").unwrap();
+
+    // The `inline-asm` feature uses `asm!` directly and needs nothing built
+    // here; without it, CSR access goes through the precompiled routines in
+    // `asm.S` so that this crate keeps working on stable.
+    if env::var_os("CARGO_FEATURE_INLINE_ASM").is_some() {
+        return;
+    }
+
+    if target.starts_with("riscv32") || target.starts_with("riscv64") {
+        cc::Build::new().file("asm.S").compile("riscv-csr");
+    }
+}
@@ -1,5 +1,4 @@
 //! mstatus register
-// TODO: Virtualization, Memory Privilege and Extension Context Fields
 
 use bit_field::BitField;
 
@@ -22,6 +21,15 @@ pub enum SPP {
     User = 0,
 }
 
+/// Extension register context status
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContextStatus {
+    Off = 0,
+    Initial = 1,
+    Clean = 2,
+    Dirty = 3,
+}
+
 impl Mstatus {
     /// User Interrupt Enable
     #[inline]
@@ -98,50 +106,223 @@ impl Mstatus {
     pub fn set_mpp(&mut self, val: MPP) {
         self.bits.set_bits(11..13, val as usize);
     }
-}
 
+    /// Supervisor Previous Privilege Mode
+    #[inline]
+    pub fn set_spp(&mut self, val: SPP) {
+        self.bits.set_bit(8, val as usize == 1);
+    }
 
-/// Reads the CSR
-#[inline]
-pub fn read() -> Mstatus {
-    match () {
-        #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
-        () => {
-            let r: usize;
-            unsafe {
-                asm!("csrrs $0, 0x300, x0" : "=r"(r) ::: "volatile");
-            }
-            Mstatus { bits: r }
+    /// Floating-Point extension context status
+    #[inline]
+    pub fn fs(&self) -> ContextStatus {
+        match self.bits.get_bits(13..15) {
+            0b00 => ContextStatus::Off,
+            0b01 => ContextStatus::Initial,
+            0b10 => ContextStatus::Clean,
+            0b11 => ContextStatus::Dirty,
+            _ => unreachable!(),
         }
-        #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
-        () => unimplemented!(),
     }
-}
 
-/// Sets the CSR
-#[cfg_attr(not(any(target_arch = "riscv32", target_arch = "riscv64")), allow(unused_variables))]
-#[inline]
-unsafe fn set(bits: usize) {
-    match () {
-        #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
-        () => asm!("csrrs x0, 0x300, $0" :: "r"(bits) :: "volatile"),
-        #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
-        () => unimplemented!(),
+    /// Additional user-mode extensions context status
+    #[inline]
+    pub fn xs(&self) -> ContextStatus {
+        match self.bits.get_bits(15..17) {
+            0b00 => ContextStatus::Off,
+            0b01 => ContextStatus::Initial,
+            0b10 => ContextStatus::Clean,
+            0b11 => ContextStatus::Dirty,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Sets the Floating-Point extension context status
+    #[inline]
+    pub fn set_fs(&mut self, fs: ContextStatus) {
+        self.bits.set_bits(13..15, fs as usize);
+    }
+
+    /// Whether either the FS or XS context is Dirty (summary bit, bit 31 on
+    /// RV32 or bit 63 on RV64)
+    #[inline]
+    pub fn sd(&self) -> bool {
+        #[cfg(target_pointer_width = "32")]
+        {
+            self.bits.get_bit(31)
+        }
+        #[cfg(target_pointer_width = "64")]
+        {
+            self.bits.get_bit(63)
+        }
+    }
+
+    /// Modify PRiVilege: loads and stores execute with the privilege in `MPP`
+    #[inline]
+    pub fn mprv(&self) -> bool {
+        self.bits.get_bit(17)
+    }
+
+    /// permit Supervisor User Memory access
+    #[inline]
+    pub fn sum(&self) -> bool {
+        self.bits.get_bit(18)
+    }
+
+    /// Make eXecutable Readable
+    #[inline]
+    pub fn mxr(&self) -> bool {
+        self.bits.get_bit(19)
+    }
+
+    /// Sets whether loads and stores execute with the privilege in `MPP`
+    #[inline]
+    pub fn set_mprv(&mut self, mprv: bool) {
+        self.bits.set_bit(17, mprv);
+    }
+
+    /// Sets whether S-mode may access U-mode pages
+    #[inline]
+    pub fn set_sum(&mut self, sum: bool) {
+        self.bits.set_bit(18, sum);
+    }
+
+    /// Sets whether executable pages are also made readable
+    #[inline]
+    pub fn set_mxr(&mut self, mxr: bool) {
+        self.bits.set_bit(19, mxr);
+    }
+
+    /// Trap Virtual Memory: traps S-mode access to satp or sfence.vma
+    #[inline]
+    pub fn tvm(&self) -> bool {
+        self.bits.get_bit(20)
+    }
+
+    /// Timeout Wait: traps S-mode WFI if it does not complete within an
+    /// implementation-defined bound
+    #[inline]
+    pub fn tw(&self) -> bool {
+        self.bits.get_bit(21)
+    }
+
+    /// Trap SRET: traps S-mode sret
+    #[inline]
+    pub fn tsr(&self) -> bool {
+        self.bits.get_bit(22)
+    }
+
+    /// Sets whether S-mode access to satp or sfence.vma is trapped
+    #[inline]
+    pub fn set_tvm(&mut self, tvm: bool) {
+        self.bits.set_bit(20, tvm);
+    }
+
+    /// Sets whether S-mode WFI traps after an implementation-defined bound
+    #[inline]
+    pub fn set_tw(&mut self, tw: bool) {
+        self.bits.set_bit(21, tw);
+    }
+
+    /// Sets whether S-mode sret is trapped
+    #[inline]
+    pub fn set_tsr(&mut self, tsr: bool) {
+        self.bits.set_bit(22, tsr);
     }
 }
 
-/// Clears the CSR
-#[cfg_attr(not(any(target_arch = "riscv32", target_arch = "riscv64")), allow(unused_variables))]
-#[inline]
-unsafe fn clear(bits: usize) {
-    match () {
-        #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
-        () => asm!("csrrc x0, 0x300, $0" :: "r"(bits) :: "volatile"),
-        #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
-        () => unimplemented!(),
+
+/// Generates the `read`/`write`/`set`/`clear`/`modify` primitives for a status-like CSR.
+///
+/// `$csr` is the CSR address (as the literal text to splice into the `asm!` strings); `$r`,
+/// `$w`, `$s`, `$c` name the `extern "C"` routines (implemented in `asm.S`, built by build.rs)
+/// used as the stable-toolchain fallback when the `inline-asm` feature is off. This lets sibling
+/// registers (e.g. `sstatus`, `hstatus`) be defined in a few lines instead of duplicating this
+/// whole block.
+macro_rules! csr_register {
+    ($Register:ident, $csr:expr, $r:ident, $w:ident, $s:ident, $c:ident) => {
+        #[cfg(not(feature = "inline-asm"))]
+        extern "C" {
+            fn $r() -> usize;
+            fn $w(bits: usize);
+            fn $s(bits: usize);
+            fn $c(bits: usize);
+        }
+
+        /// Reads the CSR
+        #[inline]
+        pub fn read() -> $Register {
+            match () {
+                #[cfg(all(feature = "inline-asm", any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => {
+                    let r: usize;
+                    unsafe {
+                        asm!(concat!("csrrs $0, ", $csr, ", x0") : "=r"(r) ::: "volatile");
+                    }
+                    $Register { bits: r }
+                }
+                #[cfg(all(not(feature = "inline-asm"), any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => $Register { bits: unsafe { $r() } },
+                #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => unimplemented!(),
+            }
+        }
+
+        /// Sets the CSR
+        #[cfg_attr(not(any(target_arch = "riscv32", target_arch = "riscv64")), allow(unused_variables))]
+        #[inline]
+        unsafe fn set(bits: usize) {
+            match () {
+                #[cfg(all(feature = "inline-asm", any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => asm!(concat!("csrrs x0, ", $csr, ", $0") :: "r"(bits) :: "volatile"),
+                #[cfg(all(not(feature = "inline-asm"), any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => $s(bits),
+                #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => unimplemented!(),
+            }
+        }
+
+        /// Clears the CSR
+        #[cfg_attr(not(any(target_arch = "riscv32", target_arch = "riscv64")), allow(unused_variables))]
+        #[inline]
+        unsafe fn clear(bits: usize) {
+            match () {
+                #[cfg(all(feature = "inline-asm", any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => asm!(concat!("csrrc x0, ", $csr, ", $0") :: "r"(bits) :: "volatile"),
+                #[cfg(all(not(feature = "inline-asm"), any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => $c(bits),
+                #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => unimplemented!(),
+            }
+        }
+
+        /// Writes the CSR
+        #[cfg_attr(not(any(target_arch = "riscv32", target_arch = "riscv64")), allow(unused_variables))]
+        #[inline]
+        pub unsafe fn write(bits: $Register) {
+            let bits = bits.bits;
+            match () {
+                #[cfg(all(feature = "inline-asm", any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => asm!(concat!("csrrw x0, ", $csr, ", $0") :: "r"(bits) :: "volatile"),
+                #[cfg(all(not(feature = "inline-asm"), any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => $w(bits),
+                #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+                () => unimplemented!(),
+            }
+        }
+
+        /// Reads the CSR, applies `f` to it, and writes the result back
+        #[inline]
+        pub unsafe fn modify<F: FnOnce(&mut $Register)>(f: F) {
+            let mut value = read();
+            f(&mut value);
+            write(value);
+        }
     }
 }
 
+csr_register!(Mstatus, "0x300", __read_mstatus, __write_mstatus, __set_mstatus, __clear_mstatus);
+
 macro_rules! set_csr {
     ($set_field:ident, $e:expr) => {
         #[inline]
@@ -184,10 +365,22 @@ set_csr!(set_xpie, 1 << 7);
 /// Supervisor Previous Privilege Mode
 #[inline]
 pub unsafe fn set_spp(spp: SPP) {
-    set((spp as usize) << 8);
+    modify(|r| r.set_spp(spp));
 }
 /// Machine Previous Privilege Mode
 #[inline]
 pub unsafe fn set_mpp(mpp: MPP) {
-    set((mpp as usize) << 11);
+    modify(|r| r.set_mpp(mpp));
 }
+/// Modify PRiVilege
+set_clear_csr!(set_mprv, clear_mprv, 1 << 17);
+/// Permit Supervisor User Memory access
+set_clear_csr!(set_sum, clear_sum, 1 << 18);
+/// Make eXecutable Readable
+set_clear_csr!(set_mxr, clear_mxr, 1 << 19);
+/// Trap Virtual Memory
+set_clear_csr!(set_tvm, clear_tvm, 1 << 20);
+/// Timeout Wait
+set_clear_csr!(set_tw, clear_tw, 1 << 21);
+/// Trap SRET
+set_clear_csr!(set_tsr, clear_tsr, 1 << 22);